@@ -2,31 +2,330 @@ use crate::parsing::{find_vocab_id, VocabId};
 use crate::{anki_connect, parsing, Config};
 use anyhow::{anyhow, Context as AnyhowContext, Result};
 use log::*;
+use lru::LruCache;
+use rand::Rng;
 use reqwest::header::HeaderValue;
 use reqwest::{Request, Response};
 use std::fmt::Display;
 use std::future::Future;
+use std::num::NonZeroUsize;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex as AsyncMutex;
 use tower::buffer::Buffer;
-use tower::limit::{ConcurrencyLimit, RateLimit};
+use tower::limit::ConcurrencyLimit;
+use tower::retry::{Policy, Retry};
 use tower::{Service, ServiceExt};
 
 pub const DOMAIN: &str = "jpdb.io";
 pub const URL_PREFIX: &str = "https://";
 
+/// Hard ceiling on the backoff delay between retries, regardless of attempt count.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Number of consecutive 429s it takes before we widen the rate limiter interval.
+const RATE_LIMIT_WIDEN_THRESHOLD: u32 = 2;
+
+/// Hard ceiling on how far the adaptive rate limiter will widen the interval.
+const MAX_RATE_LIMIT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Max number of times `RetryAfterService` will re-issue a request in
+/// response to a `Retry-After`-bearing 429/503 before giving up and handing
+/// the (still-throttled) response back to the caller.
+const MAX_RETRY_AFTER_ATTEMPTS: u32 = 5;
+
+/// Cumulative cap on time spent sleeping across all of a single request's
+/// `Retry-After` waits, so a server that keeps sending large values can't
+/// stall the single `ConcurrencyLimit` slot indefinitely.
+const MAX_RETRY_AFTER_TOTAL_WAIT: Duration = Duration::from_secs(120);
+
+/// Fallback used when `Config.definition_languages` is empty, matching the
+/// language set `set_custom_definition` used to force-enable unconditionally
+/// before it became configurable.
+const DEFAULT_DEFINITION_LANGUAGES: [&str; 6] = [
+    "english",
+    "japanese",
+    "german",
+    "spanish",
+    "french",
+    "hungarian",
+];
+
+/// Bound on the number of (word, reading) lookups kept in `JPDBConnection`'s
+/// detail-URL/vocab-id cache, so long mining sessions don't grow it unbounded.
+const DETAIL_CACHE_CAPACITY: usize = 512;
+
+/// A resolved `(word, reading)` lookup: the detail page's relative URL plus
+/// the vocab/sentence/reading ids scraped from it, cached to skip repeat
+/// `/search` and detail-page round trips for words mined more than once.
+#[derive(Clone)]
+struct CachedVocab {
+    detail_url: String,
+    v: String,
+    s: String,
+    r: String,
+}
+
 #[derive(Clone)]
 pub struct JPDBConnection {
     pub service: BufferedService,
     pub config: Config,
+    detail_cache: Arc<Mutex<LruCache<(String, String), CachedVocab>>>,
+    cookie_jar: Arc<reqwest::cookie::Jar>,
+}
+
+/// Sets the `sid` cookie on `jar` for the jpdb.io domain so the client's
+/// cookie store attaches it to every subsequent request automatically.
+fn set_session_cookie(jar: &reqwest::cookie::Jar, session_id: &str) {
+    let url = reqwest::Url::parse(&abs_url("/")).expect("abs_url always produces a valid URL");
+    jar.add_cookie_str(&format!("sid={session_id}; Domain={DOMAIN}; Path=/"), &url);
 }
 
-type BufferedService = Buffer<ConcurrencyLimit<RateLimit<ReqwestService>>, Request>;
+type BufferedService = Buffer<
+    ConcurrencyLimit<AdaptiveRateLimit<Retry<RetryPolicy, RetryAfterService<ReqwestService>>>>,
+    Request,
+>;
 
 pub struct ReqwestService {
     pub client: reqwest::Client,
 }
 
+/// Shared state behind [`AdaptiveRateLimit`] and [`RetryAfterService`]: the
+/// minimum spacing currently enforced between requests, widened temporarily
+/// when jpdb.io starts sending back-to-back 429s and relaxed once it stops.
+struct RateLimitState {
+    base_interval: Duration,
+    current_interval_ms: AtomicU64,
+    consecutive_429s: AtomicU32,
+    last_request: AsyncMutex<Instant>,
+}
+
+impl RateLimitState {
+    fn new(base_interval: Duration) -> Self {
+        Self {
+            base_interval,
+            current_interval_ms: AtomicU64::new(base_interval.as_millis() as u64),
+            consecutive_429s: AtomicU32::new(0),
+            last_request: AsyncMutex::new(Instant::now() - base_interval),
+        }
+    }
+
+    fn note_throttled(&self) {
+        let count = self.consecutive_429s.fetch_add(1, Ordering::Relaxed) + 1;
+        if count >= RATE_LIMIT_WIDEN_THRESHOLD {
+            let widened = (self.current_interval_ms.load(Ordering::Relaxed) * 2)
+                .min(MAX_RATE_LIMIT_INTERVAL.as_millis() as u64);
+            self.current_interval_ms.store(widened, Ordering::Relaxed);
+            debug!(
+                "widening rate limit interval to {}ms after repeated 429s",
+                widened
+            );
+        }
+    }
+
+    fn note_success(&self) {
+        self.consecutive_429s.store(0, Ordering::Relaxed);
+        self.current_interval_ms
+            .store(self.base_interval.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    async fn wait_turn(&self) {
+        let interval = Duration::from_millis(self.current_interval_ms.load(Ordering::Relaxed));
+        let mut last_request = self.last_request.lock().await;
+        let elapsed = last_request.elapsed();
+        if elapsed < interval {
+            tokio::time::sleep(interval - elapsed).await;
+        }
+        *last_request = Instant::now();
+    }
+}
+
+/// Enforces the (possibly widened) interval tracked by [`RateLimitState`]
+/// between requests, in place of a static `tower::limit::RateLimit`.
+#[derive(Clone)]
+pub struct AdaptiveRateLimit<S> {
+    inner: S,
+    state: Arc<RateLimitState>,
+}
+
+impl<S> Service<Request> for AdaptiveRateLimit<S>
+where
+    S: Service<Request, Response = Response, Error = reqwest::Error> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = reqwest::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, reqwest::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let state = self.state.clone();
+        let mut inner = self.inner.clone();
+        Box::pin(async move {
+            state.wait_turn().await;
+            inner.call(req).await
+        })
+    }
+}
+
+/// Re-issues a request when the response is a 429/503 carrying a `Retry-After`
+/// header, sleeping for the duration the header asks for first. This keeps
+/// `add_note`'s burst of form posts (add-to-deck, forq, unlock, set-sentence,
+/// set-definition) resilient to server-side throttling instead of failing
+/// with a raw status error.
+#[derive(Clone)]
+pub struct RetryAfterService<S> {
+    inner: S,
+    state: Arc<RateLimitState>,
+}
+
+impl<S> Service<Request> for RetryAfterService<S>
+where
+    S: Service<Request, Response = Response, Error = reqwest::Error> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = reqwest::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, reqwest::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let state = self.state.clone();
+        let mut inner = self.inner.clone();
+        Box::pin(async move {
+            let mut req = req;
+            let mut attempts = 0u32;
+            let mut total_wait = Duration::ZERO;
+            loop {
+                let Some(retryable) = req.try_clone() else {
+                    return inner.call(req).await;
+                };
+                let res = inner.call(req).await?;
+                let status = res.status();
+                let retry_after = (status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    || status == reqwest::StatusCode::SERVICE_UNAVAILABLE)
+                    .then(|| res.headers().get("retry-after"))
+                    .flatten()
+                    .and_then(parse_retry_after);
+                match retry_after {
+                    Some(delay)
+                        if attempts < MAX_RETRY_AFTER_ATTEMPTS
+                            && total_wait + delay <= MAX_RETRY_AFTER_TOTAL_WAIT =>
+                    {
+                        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                            state.note_throttled();
+                        }
+                        attempts += 1;
+                        total_wait += delay;
+                        warn!("jpdb.io throttled us ({status}), retrying in {delay:?}");
+                        tokio::time::sleep(delay).await;
+                        req = retryable;
+                    }
+                    Some(_) => {
+                        warn!(
+                            "giving up on Retry-After retries after {attempts} attempts \
+                             ({status}); returning throttled response"
+                        );
+                        return Ok(res);
+                    }
+                    None => {
+                        if status.is_success() {
+                            state.note_success();
+                        }
+                        return Ok(res);
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Parses a `Retry-After` header value, which is either a number of
+/// delta-seconds or an HTTP-date.
+fn parse_retry_after(value: &HeaderValue) -> Option<Duration> {
+    let value = value.to_str().ok()?;
+    let delay = if let Ok(seconds) = value.parse::<u64>() {
+        Duration::from_secs(seconds)
+    } else {
+        let at = httpdate::parse_http_date(value).ok()?;
+        at.duration_since(std::time::SystemTime::now())
+            .unwrap_or_default()
+    };
+    // Never trust the server to ask for an unreasonably long wait.
+    Some(delay.min(MAX_RATE_LIMIT_INTERVAL))
+}
+
+/// Retries transient failures (connect/timeout errors and 5xx responses) with
+/// exponential backoff, up to `max_retries` attempts.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    attempt: u32,
+    max_retries: u32,
+    base_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, base_delay: Duration) -> Self {
+        Self {
+            attempt: 0,
+            max_retries,
+            base_delay,
+        }
+    }
+
+    fn backoff(&self) -> Duration {
+        let exp = self.base_delay.saturating_mul(1u32 << self.attempt.min(16));
+        let jitter = Duration::from_millis(rand::thread_rng().gen_range(0..100));
+        (exp + jitter).min(MAX_RETRY_DELAY)
+    }
+}
+
+impl Policy<Request, Response, reqwest::Error> for RetryPolicy {
+    type Future = Pin<Box<dyn Future<Output = Self> + Send>>;
+
+    fn retry(
+        &self,
+        _req: &Request,
+        result: Result<&Response, &reqwest::Error>,
+    ) -> Option<Self::Future> {
+        if self.attempt >= self.max_retries {
+            return None;
+        }
+        let should_retry = match result {
+            Ok(res) => res.status().is_server_error(),
+            Err(e) => e.is_connect() || e.is_timeout(),
+        };
+        if !should_retry {
+            return None;
+        }
+        let delay = self.backoff();
+        let next = RetryPolicy {
+            attempt: self.attempt + 1,
+            max_retries: self.max_retries,
+            base_delay: self.base_delay,
+        };
+        Some(Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            next
+        }))
+    }
+
+    fn clone_request(&self, req: &Request) -> Option<Request> {
+        // Form POST bodies built by `form_request` are in-memory and always
+        // clonable; streaming bodies are not, so we give up on retrying those.
+        req.try_clone()
+    }
+}
+
 impl Service<Request> for ReqwestService {
     type Response = reqwest::Response;
     type Error = reqwest::Error;
@@ -44,13 +343,43 @@ impl Service<Request> for ReqwestService {
 
 pub async fn send_request(service: &mut BufferedService, req: Request) -> Result<Response> {
     trace!("Request url: {}", req.url());
+    let url = req.url().clone();
     service
         .ready()
         .await
         .map_err(|e| anyhow!("error getting reqwest client {e}"))?
         .call(req)
         .await
-        .map_err(|e| anyhow!("{e}")) // we use this mapping to make our error type sized
+        .map_err(|e| {
+            // we use this mapping to make our error type sized
+            match e.downcast_ref::<reqwest::Error>() {
+                Some(e) if e.is_timeout() => anyhow!("request to {url} timed out"),
+                _ => anyhow!("{e}"),
+            }
+        })
+}
+
+/// Pulls the `sid` value out of a single `Set-Cookie` header, if present.
+fn parse_sid_cookie(set_cookie: &str) -> Option<String> {
+    set_cookie
+        .split(';')
+        .next()?
+        .split_once('=')
+        .filter(|(name, _)| name.trim() == "sid")
+        .map(|(_, value)| value.trim().to_string())
+}
+
+/// True if `res` is a redirect whose `Location` points back at `/login`,
+/// meaning the session expired between this request and the last one that
+/// used it. Every write we make after login needs to check for this, since
+/// the session can lapse at any point in a long-running `add_note` call.
+fn is_login_redirect(res: &Response) -> bool {
+    res.status().is_redirection()
+        && res
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .map_or(false, |loc| loc.contains("/login"))
 }
 
 pub fn abs_url(rel: impl Display) -> String {
@@ -80,6 +409,122 @@ pub async fn form_request(
 }
 
 impl JPDBConnection {
+    pub fn new(config: Config) -> Result<Self> {
+        let cookie_jar = Arc::new(reqwest::cookie::Jar::default());
+        if let Some(session_id) = &config.session_id {
+            set_session_cookie(&cookie_jar, session_id);
+        }
+
+        let mut client_builder = reqwest::Client::builder()
+            .gzip(true)
+            .brotli(true)
+            .cookie_provider(cookie_jar.clone())
+            // `set_custom_sentence`/`set_custom_definition`/etc. treat a 3xx
+            // response as success, which only holds if we observe the
+            // redirect ourselves instead of letting reqwest follow it; this
+            // is also what lets `add_note` notice a session-expired redirect
+            // back to `/login` and re-authenticate.
+            .redirect(reqwest::redirect::Policy::none());
+        if let Some(timeout) = config.timeout {
+            client_builder = client_builder.timeout(timeout);
+        }
+        if let Some(proxy_url) = &config.proxy {
+            let proxy = reqwest::Proxy::all(proxy_url).context("invalid proxy URL")?;
+            client_builder = client_builder.proxy(proxy);
+        }
+        let client = client_builder.build().context("building reqwest client")?;
+
+        let rate_limit_state = Arc::new(RateLimitState::new(Duration::from_secs(1)));
+        let retry_policy = RetryPolicy::new(config.max_retries, config.base_delay);
+        let service = ReqwestService { client };
+        let service = RetryAfterService {
+            inner: service,
+            state: rate_limit_state.clone(),
+        };
+        let service = Retry::new(retry_policy, service);
+        let service = AdaptiveRateLimit {
+            inner: service,
+            state: rate_limit_state,
+        };
+        let service = ConcurrencyLimit::new(service, 1);
+        let service = Buffer::new(service, 100);
+        let detail_cache = Arc::new(Mutex::new(LruCache::new(
+            NonZeroUsize::new(DETAIL_CACHE_CAPACITY).unwrap(),
+        )));
+        Ok(JPDBConnection {
+            service,
+            config,
+            detail_cache,
+            cookie_jar,
+        })
+    }
+
+    /// Performs the real browser login flow: GET `/login`, scrape the CSRF
+    /// token out of the form, POST credentials, and capture the `sid` cookie
+    /// from the response to populate `session_id` for the rest of the
+    /// connection's lifetime.
+    pub async fn login(&mut self) -> Result<()> {
+        let (email, password) = self
+            .config
+            .credentials
+            .as_ref()
+            .context("login requires email/password in Config")?;
+
+        let res = get_request(&mut self.service, "/login")
+            .await
+            .context("get login page")?;
+        let body = res.text().await?;
+        let csrf_token = parsing::find_login_token(&body).context("can't find login token")?;
+
+        let payload: [(&str, &str); 3] = [
+            ("csrf_token", &csrf_token),
+            ("email", email),
+            ("password", password),
+        ];
+        let res = form_request(&mut self.service, "/login", payload)
+            .await
+            .context("login request")?;
+
+        let sid = res
+            .headers()
+            .get_all(reqwest::header::SET_COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .find_map(parse_sid_cookie)
+            .context("login response did not set a session cookie")?;
+        info!("Logged in to jpdb.io");
+        set_session_cookie(&self.cookie_jar, &sid);
+        self.config.session_id = Some(sid);
+        Ok(())
+    }
+
+    /// Logs in if we have credentials but no session yet.
+    async fn ensure_logged_in(&mut self) -> Result<()> {
+        if self.config.session_id.is_none() && self.config.credentials.is_some() {
+            self.login().await?;
+        }
+        Ok(())
+    }
+
+    /// POSTs `payload` to `rel_url`, retrying once after a fresh login if the
+    /// response turns out to be a session-expiry redirect back to `/login`.
+    /// `add_note` can run many writes (add-to-deck, forq, unlock, sentence,
+    /// definition) for a single card, and the session can expire partway
+    /// through that sequence, so every one of those writes goes through here
+    /// rather than calling `form_request` directly.
+    async fn form_request_reauth(
+        &mut self,
+        rel_url: &str,
+        payload: impl serde::Serialize + Clone,
+    ) -> Result<Response> {
+        let res = form_request(&mut self.service, rel_url, payload.clone()).await?;
+        if is_login_redirect(&res) {
+            self.login().await?;
+            return form_request(&mut self.service, rel_url, payload).await;
+        }
+        Ok(res)
+    }
+
     pub async fn add_note(&mut self, s: &anki_connect::Fields) -> Result<String> {
         debug!(
             "add W='{}' R='{}' S='{}' D='{}'",
@@ -89,63 +534,116 @@ impl JPDBConnection {
             s.definition.as_deref().unwrap_or_default(),
         );
 
+        self.ensure_logged_in().await?;
+
+        let reading = s.reading.as_deref().unwrap_or_default();
+        let cache_key = (s.word.clone(), reading.to_string());
+        let cached = if self.config.cache_lookups {
+            self.detail_cache.lock().unwrap().get(&cache_key).cloned()
+        } else {
+            None
+        };
+
         let url = format!("https://jpdb.io/search?q={}&lang=english#a", s.word);
 
-        let req = Request::new(reqwest::Method::GET, reqwest::Url::parse(&url)?);
-        let res = send_request(&mut self.service, req)
-            .await
-            .context("search request")?;
-        let body = &res.text().await?;
-        let detail_url =
-            parsing::find_detail_url(body, &s.word, s.reading.as_deref().unwrap_or_default());
+        let detail_url = if let Some(cached) = &cached {
+            debug!("cache hit for '{}' ({})", s.word, reading);
+            Ok(cached.detail_url.clone())
+        } else {
+            let req = Request::new(reqwest::Method::GET, reqwest::Url::parse(&url)?);
+            let res = send_request(&mut self.service, req)
+                .await
+                .context("search request")?;
+
+            // The session may have expired mid-run; an observed redirect back
+            // to `/login` means we need to log in again and retry once.
+            let res = if is_login_redirect(&res) {
+                self.login().await?;
+                let req = Request::new(reqwest::Method::GET, reqwest::Url::parse(&url)?);
+                send_request(&mut self.service, req)
+                    .await
+                    .context("search request (after re-login)")?
+            } else {
+                res
+            };
+            let body = res.text().await?;
+            parsing::find_detail_url(&body, &s.word, reading).map(|u| u.to_string())
+        };
 
         let open_url = if let Ok(ref rel_url) = &detail_url {
             format!("{}{}{}", URL_PREFIX, DOMAIN, rel_url)
         } else {
             info!("Can't find details page for: {}", s.word);
-            url.into()
+            url.clone()
         };
 
         if self.config.session_id.is_some() {
             if let Ok(ref detail_url) = detail_url {
-                // look up vocab id on details page
-                let res = get_request(&mut self.service, detail_url)
-                    .await
-                    .context("get detail page")?;
-                let body = &res.text().await?;
-                trace!("Details page:");
-                trace!("{}", body);
-                let vocab = VocabCard { body };
+                // A cached lookup only carries the vocab id, not the page body,
+                // so retaining existing definitions (which needs the current
+                // shown meanings) still requires a fresh detail-page fetch --
+                // but only for notes that actually set a definition.
+                let needs_body = self.config.add_custom_definition
+                    && self.config.retain_definitions
+                    && s.definition.is_some();
+                let body_storage;
+                let vocab = match &cached {
+                    Some(cached) if !needs_body => VocabCard::from_cached(cached),
+                    _ => {
+                        let res = get_request(&mut self.service, detail_url)
+                            .await
+                            .context("get detail page")?;
+                        body_storage = res.text().await?;
+                        trace!("Details page:");
+                        trace!("{}", body_storage);
+                        VocabCard::from_body(&body_storage)?
+                    }
+                };
+                if self.config.cache_lookups {
+                    let (v, s, r) = vocab.id_tuple();
+                    self.detail_cache.lock().unwrap().put(
+                        cache_key,
+                        CachedVocab {
+                            detail_url: detail_url.clone(),
+                            v,
+                            s,
+                            r,
+                        },
+                    );
+                }
                 if let Some(deck_id) = self.config.auto_add {
                     info!("Adding card to deck: {}", abs_url(detail_url));
-                    vocab
-                        .add_to_deck(&mut self.service, deck_id, &detail_url)
-                        .await?;
+                    vocab.add_to_deck(self, deck_id, &detail_url).await?;
                 }
                 if self.config.auto_unlock {
                     info!("unlocking: {}", abs_url(detail_url));
-                    vocab.force_unlock(&mut self.service, &detail_url).await?;
+                    vocab.force_unlock(self, &detail_url).await?;
                 }
                 if self.config.auto_forq {
                     // it appears we don't need to check whether for FORQing is possible
                     info!("FORQing: {}", abs_url(detail_url));
-                    vocab.forq(&mut self.service, &detail_url).await?;
+                    vocab.forq(self, &detail_url).await?;
                 }
                 if self.config.auto_forget {
                     info!("Mark unknown: {}", abs_url(detail_url));
-                    vocab.mark_unknown(&mut self.service, &detail_url).await?;
+                    vocab.mark_unknown(self, &detail_url).await?;
                 }
                 if self.config.add_mined_sentences {
                     info!("Add custom sentence: {}", abs_url(detail_url));
-                    vocab
-                        .set_custom_sentence(&mut self.service, &s.sentence)
-                        .await?;
+                    vocab.set_custom_sentence(self, &s.sentence).await?;
                 }
                 if self.config.add_custom_definition {
                     if let Some(definition) = &s.definition {
                         info!("Add custom definition: {}", abs_url(detail_url));
+                        let definition_languages = self.config.definition_languages.clone();
+                        let retain_existing = self.config.retain_definitions;
                         vocab
-                            .set_custom_definition(&mut self.service, &definition)
+                            .set_custom_definition(
+                                self,
+                                definition,
+                                &definition_languages,
+                                retain_existing,
+                            )
                             .await?;
                     }
                 }
@@ -166,17 +664,40 @@ impl JPDBConnection {
 }
 
 struct VocabCard<'a> {
-    body: &'a str,
+    // Present when constructed from a freshly-fetched detail page; `None`
+    // when constructed from a cached lookup, since we didn't re-fetch the page.
+    body: Option<&'a str>,
+    id: (String, String, String), // (v, s, r)
 }
 
-impl VocabCard<'_> {
+impl<'a> VocabCard<'a> {
+    fn from_body(body: &'a str) -> Result<Self> {
+        let VocabId { v, s, r } = find_vocab_id(body).context("can't find vocab id")?;
+        Ok(Self {
+            body: Some(body),
+            id: (v, s, r),
+        })
+    }
+
+    fn from_cached(cached: &CachedVocab) -> Self {
+        Self {
+            body: None,
+            id: (cached.v.clone(), cached.s.clone(), cached.r.clone()),
+        }
+    }
+
+    fn id_tuple(&self) -> (String, String, String) {
+        self.id.clone()
+    }
+
     fn find_id(&self) -> Result<VocabId> {
-        Ok(find_vocab_id(self.body).context("can't find vocab id")?)
+        let (v, s, r) = self.id.clone();
+        Ok(VocabId { v, s, r })
     }
 
     async fn add_to_deck(
         &self,
-        service: &mut BufferedService,
+        conn: &mut JPDBConnection,
         deck_id: u64,
         origin: &str,
     ) -> Result<()> {
@@ -189,23 +710,18 @@ impl VocabCard<'_> {
             ("origin", origin),
         ];
 
-        let res = form_request(service, &add_url, payload)
+        let res = conn
+            .form_request_reauth(&add_url, payload)
             .await
             .context("add to deck")?;
-        if !res.status().is_success() {
-            return Err(anyhow!(
-                "Add to deck failed, status: {}",
-                res.status().as_u16()
-            ));
+        let status = res.status();
+        if !status.is_success() && !status.is_redirection() {
+            return Err(anyhow!("Add to deck failed, status: {}", status.as_u16()));
         }
         Ok(())
     }
 
-    async fn set_custom_sentence(
-        &self,
-        service: &mut BufferedService,
-        sentence: &str,
-    ) -> Result<()> {
+    async fn set_custom_sentence(&self, conn: &mut JPDBConnection, sentence: &str) -> Result<()> {
         debug!("custom sentence: {}", sentence);
         if sentence.len() < 1 {
             info!("Sentence field was empty. Will not set custom sentence.");
@@ -215,7 +731,8 @@ impl VocabCard<'_> {
         let VocabId { v, s, r } = vocab_id;
         let edit_sentence_url = format!("/edit-shown-sentence?v={}&s={}&r={}", v, s, r);
         let payload: [(&str, &str); 2] = [("sentence", sentence), ("translation", "")];
-        let res = form_request(service, &edit_sentence_url, payload)
+        let res = conn
+            .form_request_reauth(&edit_sentence_url, payload)
             .await
             .context("set custom sentence request")?;
         let status = res.status();
@@ -231,12 +748,11 @@ impl VocabCard<'_> {
 
     async fn set_custom_definition(
         &self,
-        service: &mut BufferedService,
+        conn: &mut JPDBConnection,
         definition: &str,
+        definition_languages: &[String],
+        retain_existing: bool,
     ) -> Result<()> {
-        // TODO: Add option to retain original definitions
-        // The api overwrites the full list of shown definitions
-        // Doing this would require fetching the list first
         debug!("custom definition: {}", definition);
         if definition.len() < 1 {
             info!("Definition field was empty. Will not update definitions.");
@@ -245,22 +761,48 @@ impl VocabCard<'_> {
         let vocab_id = self.find_id()?;
         let VocabId { v, s, r } = vocab_id;
         let edit_definition_url = format!("/edit_shown_meanings?v={}&s={}&r={}", v, s, r);
-        // TODO:
-        // If no language-xxx field is sent, the update fails silently, so we must send some.
-        // I don't want to default to only english in case that causes problems for some users
-        // I don't know what happens if a word is missing a definition server side, say hungarian,
-        // If language-hungarian is set to 1 and there is no existing hungarian definition there may be an error
-        let payload: [(&str, &str); 8] = [
-            ("language-select", "default"),
-            ("language-english", "1"),
-            ("language-japanese", "1"),
-            ("language-german", "1"),
-            ("language-spanish", "1"),
-            ("language-french", "1"),
-            ("language-hungarian", "1"),
-            ("custom-definition", definition),
-        ];
-        let res = form_request(service, &edit_definition_url, payload)
+        // If no language-xxx field is sent, the update fails silently, so we
+        // always send the user's configured `definition_languages` rather
+        // than guessing and risking either a silent no-op or force-enabling
+        // a language the user never uses. An empty config falls back to the
+        // full set this used to force-enable unconditionally, so users who
+        // haven't configured `definition_languages` keep their old overwrite
+        // behavior instead of silently narrowing to a single language.
+        let mut languages: Vec<String> = if definition_languages.is_empty() {
+            DEFAULT_DEFINITION_LANGUAGES
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        } else {
+            definition_languages.to_vec()
+        };
+        let mut payload: Vec<(String, String)> =
+            vec![("language-select".to_string(), "default".to_string())];
+
+        if retain_existing {
+            // `/edit_shown_meanings` overwrites the full list of shown
+            // definitions, so re-send each existing meaning alongside the
+            // new one instead of clobbering them. Callers with
+            // `retain_definitions` set always fetch a fresh page body (see
+            // `add_note`), so this should only be `None` if that invariant
+            // is violated elsewhere.
+            let body = self
+                .body
+                .context("retain_definitions requires a loaded page body")?;
+            for meaning in parsing::find_shown_meanings(body) {
+                if !languages.contains(&meaning.language) {
+                    languages.push(meaning.language.clone());
+                }
+                payload.push(("custom-definition".to_string(), meaning.text));
+            }
+        }
+        payload.push(("custom-definition".to_string(), definition.to_string()));
+        for language in &languages {
+            payload.push((format!("language-{}", language), "1".to_string()));
+        }
+
+        let res = conn
+            .form_request_reauth(&edit_definition_url, payload)
             .await
             .context("set custom definition request")?;
         let status = res.status();
@@ -274,11 +816,12 @@ impl VocabCard<'_> {
         Ok(())
     }
 
-    async fn forq(&self, service: &mut BufferedService, origin: &str) -> Result<()> {
+    async fn forq(&self, conn: &mut JPDBConnection, origin: &str) -> Result<()> {
         let vocab_id = self.find_id()?;
         let payload: [(&str, &str); 3] =
             [("v", &vocab_id.v), ("s", &vocab_id.s), ("origin", origin)];
-        let res = form_request(service, "/prioritize", payload)
+        let res = conn
+            .form_request_reauth("/prioritize", payload)
             .await
             .context("forq request")?;
         let status = res.status();
@@ -289,11 +832,12 @@ impl VocabCard<'_> {
         Ok(())
     }
 
-    async fn force_unlock(&self, service: &mut BufferedService, origin: &str) -> Result<()> {
+    async fn force_unlock(&self, conn: &mut JPDBConnection, origin: &str) -> Result<()> {
         let vocab_id = self.find_id()?;
         let payload: [(&str, &str); 3] =
             [("v", &vocab_id.v), ("s", &vocab_id.s), ("origin", origin)];
-        let res = form_request(service, "/force-unlock", payload)
+        let res = conn
+            .form_request_reauth("/force-unlock", payload)
             .await
             .context("force-unlock request")?;
         let status = res.status();
@@ -304,11 +848,12 @@ impl VocabCard<'_> {
         Ok(())
     }
 
-    async fn mark_unknown(&self, service: &mut BufferedService, origin: &str) -> Result<()> {
+    async fn mark_unknown(&self, conn: &mut JPDBConnection, origin: &str) -> Result<()> {
         let vocab_id = self.find_id()?;
         let payload: [(&str, &str); 3] =
             [("v", &vocab_id.v), ("s", &vocab_id.s), ("origin", origin)];
-        let res = form_request(service, "/mark-as-not-known", payload)
+        let res = conn
+            .form_request_reauth("/mark-as-not-known", payload)
             .await
             .context("force-unlock request")?;
         let status = res.status();